@@ -1,4 +1,5 @@
-use super::changes::{Changes};
+use super::changes::{Changes, ContractConfig, DirectoryCreation, FileCreation, TOMLEdition};
+use std::collections::HashMap;
 
 pub struct GetChangesForNewNotebook {
     pub project_path: String,
@@ -16,6 +17,62 @@ impl GetChangesForNewNotebook {
     }
 
     pub fn run(&self) -> Vec<Changes> {
-        self.changes.clone()
+        let mut changes = self.changes.clone();
+        changes.push(self.create_notebooks_directory());
+        changes.push(self.create_template_notebook());
+        changes.push(self.index_notebook_in_clarinet_toml());
+        changes
+    }
+
+    fn create_notebooks_directory(&self) -> Changes {
+        let dir = format!("{}/notebooks", self.project_path);
+        Changes::AddDirectory(DirectoryCreation {
+            comment: format!("Creating directory {}", dir),
+            name: "notebooks".into(),
+            path: dir,
+        })
+    }
+
+    fn create_template_notebook(&self) -> Changes {
+        let notebook_file_name = format!("{}.clar", self.notebook_name);
+        let notebook_path = format!("{}/notebooks/{}", self.project_path, notebook_file_name);
+        let content = format!(
+            r#";; {}
+;; Notebook generated by `clarinet new notebook`.
+;;
+;; Use cells to explore and document your contracts interactively.
+
+"#,
+            self.notebook_name
+        );
+        Changes::AddFile(FileCreation {
+            comment: format!("Creating file {}", notebook_path),
+            name: notebook_file_name,
+            content,
+            path: notebook_path,
+        })
+    }
+
+    fn index_notebook_in_clarinet_toml(&self) -> Changes {
+        let manifest_path = format!("{}/Clarinet.toml", self.project_path);
+
+        let mut contracts_to_add = HashMap::new();
+        contracts_to_add.insert(
+            self.notebook_name.clone(),
+            ContractConfig {
+                path: format!("notebooks/{}.clar", self.notebook_name),
+                depends_on: vec![],
+            },
+        );
+
+        Changes::EditTOML(TOMLEdition {
+            comment: format!(
+                "Adding notebook {} to manifest {}",
+                self.notebook_name, manifest_path
+            ),
+            manifest_path,
+            contracts_to_add,
+            requirements_to_add: vec![],
+        })
     }
 }
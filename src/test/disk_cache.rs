@@ -3,6 +3,9 @@
 use super::fs_util;
 use super::http_cache::url_to_filename;
 use deno_core::url::{Host, Url};
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs;
 use std::io;
@@ -11,12 +14,75 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::path::Prefix;
 use std::str;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 #[derive(Clone)]
 pub struct DiskCache {
   pub location: PathBuf,
 }
 
+/// Extension used for the HTTP metadata sidecar written next to a cached body.
+const METADATA_EXTENSION: &str = "metadata.json";
+
+/// Metadata persisted alongside a cached remote resource so that subsequent
+/// loads can issue a conditional request and reuse the body on `304`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpCacheMetadata {
+  /// The request URL the body was fetched from.
+  pub url: String,
+  /// The HTTP status of the response that produced the cached body.
+  pub status: u16,
+  /// `ETag` header, if the server supplied one.
+  pub etag: Option<String>,
+  /// `Last-Modified` header, if the server supplied one.
+  pub last_modified: Option<String>,
+  /// `Cache-Control` header, if the server supplied one.
+  pub cache_control: Option<String>,
+  /// Unix timestamp (seconds) of the last time the entry was written or
+  /// revalidated.
+  pub cached_at: u64,
+}
+
+impl HttpCacheMetadata {
+  /// `headers` may arrive with arbitrary casing; HTTP header names are
+  /// case-insensitive, so normalize to lowercase keys before reading the
+  /// validators. Otherwise an `ETag`-cased entry would read back as `None` and
+  /// conditional revalidation would never fire.
+  fn new(
+    url: &Url,
+    status: u16,
+    headers: &HashMap<String, String>,
+  ) -> Self {
+    let normalized: HashMap<String, String> = headers
+      .iter()
+      .map(|(k, v)| (k.to_ascii_lowercase(), v.clone()))
+      .collect();
+    let header = |name: &str| normalized.get(name).cloned();
+    Self {
+      url: url.to_string(),
+      status,
+      etag: header("etag"),
+      last_modified: header("last-modified"),
+      cache_control: header("cache-control"),
+      cached_at: now_as_unix_secs(),
+    }
+  }
+}
+
+/// A cached body together with the metadata needed to revalidate it.
+pub struct CachedHttpResource {
+  pub body: Vec<u8>,
+  pub metadata: HttpCacheMetadata,
+}
+
+fn now_as_unix_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
 fn with_io_context<T: AsRef<str>>(
   e: &std::io::Error,
   context: T,
@@ -25,12 +91,16 @@ fn with_io_context<T: AsRef<str>>(
 }
 
 impl DiskCache {
-  /// `location` must be an absolute path.
-  pub fn new(location: &Path) -> Self {
-    assert!(location.is_absolute());
-    Self {
-      location: location.to_owned(),
-    }
+  /// Creates a cache rooted at `location`. A relative `location` is resolved
+  /// against the current working directory; resolution failures surface as an
+  /// [`io::Error`] rather than aborting the process.
+  pub fn new(location: &Path) -> io::Result<Self> {
+    let location = if location.is_absolute() {
+      location.to_owned()
+    } else {
+      std::env::current_dir()?.join(location)
+    };
+    Ok(Self { location })
   }
 
   /// Ensures the location of the cache.
@@ -46,7 +116,10 @@ impl DiskCache {
     })
   }
 
-  fn get_cache_filename(&self, url: &Url) -> Option<PathBuf> {
+  fn get_cache_filename(
+    &self,
+    url: &Url,
+  ) -> Result<PathBuf, CacheFilenameError> {
     let mut out = PathBuf::new();
 
     let scheme = url.scheme();
@@ -67,11 +140,16 @@ impl DiskCache {
           out.push(path_seg);
         }
       }
-      "http" | "https" | "data" => out = url_to_filename(url)?,
+      "http" | "https" | "data" | "blob" => {
+        out = url_to_filename(url)
+          .ok_or_else(|| CacheFilenameError::Unroutable(url.to_string()))?
+      }
       "file" => {
         let path = match url.to_file_path() {
           Ok(path) => path,
-          Err(_) => return None,
+          Err(_) => {
+            return Err(CacheFilenameError::Unroutable(url.to_string()))
+          }
         };
         let mut path_components = path.components();
 
@@ -108,31 +186,45 @@ impl DiskCache {
 
         out = out.join(remaining_components);
       }
-      _ => return None,
+      scheme => {
+        // Surface the unsupported scheme as a typed error so callers can tell
+        // an unroutable specifier apart from a plain cache miss in code rather
+        // than silently dropping the resource.
+        log::warn!(
+          "unsupported scheme '{}' for cache filename of '{}'",
+          scheme,
+          url
+        );
+        return Err(CacheFilenameError::UnsupportedScheme(scheme.to_string()));
+      }
     };
 
-    Some(out)
+    Ok(out)
   }
 
   pub fn get_cache_filename_with_extension(
     &self,
     url: &Url,
     extension: &str,
-  ) -> Option<PathBuf> {
+  ) -> Result<PathBuf, CacheFilenameError> {
     let base = self.get_cache_filename(url)?;
 
     match base.extension() {
-      None => Some(base.with_extension(extension)),
+      None => Ok(base.with_extension(extension)),
       Some(ext) => {
         let original_extension = OsStr::to_str(ext).unwrap();
         let final_extension = format!("{}.{}", original_extension, extension);
-        Some(base.with_extension(final_extension))
+        Ok(base.with_extension(final_extension))
       }
     }
   }
 
   pub fn get(&self, filename: &Path) -> std::io::Result<Vec<u8>> {
     let path = self.location.join(filename);
+    // Resolve symlinks so that two specifiers pointing through a symlinked
+    // cache location (common on macOS `/tmp`, or a symlinked vendor dir) map
+    // to the same underlying file and are treated identically.
+    let path = canonicalize_path(&path)?;
     fs::read(&path)
   }
 
@@ -145,4 +237,429 @@ impl DiskCache {
     fs_util::atomic_write_file(&path, data, super::http_cache::CACHE_PERM)
       .map_err(|e| with_io_context(&e, format!("{:#?}", &path)))
   }
+
+  fn metadata_filename(
+    &self,
+    url: &Url,
+  ) -> Result<PathBuf, CacheFilenameError> {
+    self.get_cache_filename_with_extension(url, METADATA_EXTENSION)
+  }
+
+  /// Store a fetched remote resource along with a sibling metadata file that
+  /// records the validators needed for conditional revalidation.
+  pub fn set_http(
+    &self,
+    url: &Url,
+    status: u16,
+    headers: &HashMap<String, String>,
+    data: &[u8],
+  ) -> std::io::Result<()> {
+    let body_filename = self.get_cache_filename(url)?;
+    let metadata_filename = self.metadata_filename(url)?;
+
+    self.set(&body_filename, data)?;
+
+    let metadata = HttpCacheMetadata::new(url, status, headers);
+    let serialized = serde_json::to_vec_pretty(&metadata)
+      .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    self.set(&metadata_filename, &serialized)
+  }
+
+  /// Load a cached body together with the metadata a fetcher needs to send a
+  /// conditional `GET` (`If-None-Match` / `If-Modified-Since`).
+  pub fn get_http(&self, url: &Url) -> std::io::Result<CachedHttpResource> {
+    let body_filename = self.get_cache_filename(url)?;
+    let metadata_filename = self.metadata_filename(url)?;
+
+    let body = self.get(&body_filename)?;
+    let raw_metadata = self.get(&metadata_filename)?;
+    let metadata: HttpCacheMetadata = serde_json::from_slice(&raw_metadata)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(CachedHttpResource { body, metadata })
+  }
+
+  /// Refresh the stored metadata timestamp after a `304 Not Modified`, keeping
+  /// the previously cached body in place.
+  pub fn revalidate_http(&self, url: &Url) -> std::io::Result<()> {
+    let metadata_filename = self.metadata_filename(url)?;
+
+    let raw_metadata = self.get(&metadata_filename)?;
+    let mut metadata: HttpCacheMetadata =
+      serde_json::from_slice(&raw_metadata)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    metadata.cached_at = now_as_unix_secs();
+
+    let serialized = serde_json::to_vec_pretty(&metadata)
+      .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    self.set(&metadata_filename, &serialized)
+  }
+}
+
+/// Resolve a path through any intermediate symlinks, stripping the Windows
+/// verbatim (`\\?\`) prefix that [`fs::canonicalize`] prepends so the result
+/// stays comparable with non-canonicalized paths elsewhere.
+fn canonicalize_path(path: &Path) -> io::Result<PathBuf> {
+  let canonicalized = fs::canonicalize(path)?;
+  Ok(strip_unc_prefix(canonicalized))
+}
+
+#[cfg(windows)]
+fn strip_unc_prefix(path: PathBuf) -> PathBuf {
+  use std::path::Component;
+  let mut components = path.components();
+  if let Some(Component::Prefix(prefix)) = components.next() {
+    if let Prefix::VerbatimDisk(_) = prefix.kind() {
+      // Reconstruct as a plain disk path, e.g. `\\?\C:\foo` -> `C:\foo`.
+      let bytes = prefix.as_os_str().to_string_lossy();
+      let disk = &bytes[r"\\?\".len()..];
+      let mut out = PathBuf::from(format!("{}\\", disk));
+      out.extend(components);
+      return out;
+    }
+    if let Prefix::VerbatimUNC(server, share) = prefix.kind() {
+      // Reconstruct as a plain UNC path, e.g. `\\?\UNC\srv\share\foo` ->
+      // `\\srv\share\foo`.
+      let mut out = PathBuf::from(format!(
+        r"\\{}\{}\",
+        server.to_string_lossy(),
+        share.to_string_lossy()
+      ));
+      out.extend(components);
+      return out;
+    }
+  }
+  path
+}
+
+#[cfg(not(windows))]
+fn strip_unc_prefix(path: PathBuf) -> PathBuf {
+  path
+}
+
+fn unsupported_scheme_error(url: &Url) -> io::Error {
+  io::Error::new(
+    io::ErrorKind::Unsupported,
+    format!("unsupported cache scheme for url '{}'", url),
+  )
+}
+
+/// Why a URL could not be routed to a cache filename. Lets callers tell an
+/// unsupported scheme apart from a plain cache miss (a read that returns
+/// `NotFound`) instead of collapsing both into `None`.
+#[derive(Debug, Clone)]
+pub enum CacheFilenameError {
+  /// The URL scheme is not one the cache knows how to route.
+  UnsupportedScheme(String),
+  /// The scheme is supported but the URL could not be turned into a path
+  /// (e.g. a `file:` URL that is not a valid local path).
+  Unroutable(String),
+}
+
+impl std::fmt::Display for CacheFilenameError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      CacheFilenameError::UnsupportedScheme(scheme) => {
+        write!(f, "unsupported cache scheme '{}'", scheme)
+      }
+      CacheFilenameError::Unroutable(url) => {
+        write!(f, "could not route url '{}' to a cache path", url)
+      }
+    }
+  }
+}
+
+impl std::error::Error for CacheFilenameError {}
+
+impl From<CacheFilenameError> for io::Error {
+  fn from(e: CacheFilenameError) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, e.to_string())
+  }
+}
+
+/// Name of the URL→path manifest stored at the root of a vendor directory.
+const VENDOR_MANIFEST: &str = "manifest.json";
+
+/// An entry in the vendor manifest: where a URL was stored relative to the
+/// vendor root, plus the response headers captured at fetch time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VendorManifestEntry {
+  pub path: String,
+  pub headers: HashMap<String, String>,
+}
+
+/// A project-local cache that vendors fetched remote contracts into a
+/// `vendor/` directory with a human-readable layout (`host/path` segments with
+/// the original file extension preserved) so they can be committed for
+/// reproducible builds.
+///
+/// Because some URL characters (query strings, ports, reserved characters)
+/// cannot appear in filenames, `manifest.json` is the source of truth for
+/// URL→path resolution; the global [`DiskCache`] remains the fallback.
+#[derive(Clone)]
+pub struct LocalDiskCache {
+  pub vendor_path: PathBuf,
+  fallback: DiskCache,
+}
+
+impl LocalDiskCache {
+  pub fn new(vendor_path: &Path, fallback: DiskCache) -> Self {
+    Self {
+      vendor_path: vendor_path.to_owned(),
+      fallback,
+    }
+  }
+
+  fn manifest_path(&self) -> PathBuf {
+    self.vendor_path.join(VENDOR_MANIFEST)
+  }
+
+  fn load_manifest(&self) -> io::Result<HashMap<String, VendorManifestEntry>> {
+    match fs::read(self.manifest_path()) {
+      Ok(bytes) => serde_json::from_slice(&bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+      Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+        Ok(HashMap::new())
+      }
+      Err(e) => Err(e),
+    }
+  }
+
+  fn save_manifest(
+    &self,
+    manifest: &HashMap<String, VendorManifestEntry>,
+  ) -> io::Result<()> {
+    let serialized = serde_json::to_vec_pretty(manifest)
+      .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let path = self.manifest_path();
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    fs_util::atomic_write_file(&path, &serialized, super::http_cache::CACHE_PERM)
+  }
+
+  /// Build the human-readable relative path a URL vendors to: `host` (with the
+  /// port appended when present) followed by the sanitized path segments, with
+  /// the original file extension preserved.
+  ///
+  /// The leaf filename is suffixed with a short hash of the *full* URL so the
+  /// mapping stays injective: two URLs that differ only in query string,
+  /// fragment, or trailing slash (`c.clar?v=1` vs `c.clar?v=2`, `h/` vs
+  /// `h/index`) vendor to distinct files and cannot clobber one another's body.
+  fn readable_relative_path(url: &Url) -> Option<PathBuf> {
+    let host = url.host_str()?;
+    let mut out = PathBuf::new();
+    match url.port() {
+      Some(port) => out.push(format!("{}_PORT{}", host, port)),
+      None => out.push(host),
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let trailing_slash = segments.last() == Some(&"");
+    let non_empty: Vec<&str> =
+      segments.into_iter().filter(|s| !s.is_empty()).collect();
+
+    // The final non-empty segment is the leaf filename; anything before it is a
+    // directory. A trailing slash (or an empty path) has no filename of its
+    // own, so the whole path is directories and the leaf is synthesized.
+    let (dir_parts, leaf): (&[&str], String) =
+      if trailing_slash || non_empty.is_empty() {
+        (&non_empty[..], "index".to_string())
+      } else {
+        let (last, rest) = non_empty.split_last().unwrap();
+        (rest, sanitize_segment(last))
+      };
+    for part in dir_parts {
+      out.push(sanitize_segment(part));
+    }
+
+    out.push(unique_leaf(&leaf, url));
+    Some(out)
+  }
+
+  /// Vendor a fetched remote resource, writing the body under the readable
+  /// layout and recording its URL→path mapping and headers in the manifest.
+  pub fn set(
+    &self,
+    url: &Url,
+    headers: &HashMap<String, String>,
+    data: &[u8],
+  ) -> io::Result<()> {
+    let relative = Self::readable_relative_path(url)
+      .ok_or_else(|| unsupported_scheme_error(url))?;
+    let path = self.vendor_path.join(&relative);
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    fs_util::atomic_write_file(&path, data, super::http_cache::CACHE_PERM)?;
+
+    let mut manifest = self.load_manifest()?;
+    manifest.insert(
+      url.to_string(),
+      VendorManifestEntry {
+        path: relative.to_string_lossy().replace('\\', "/"),
+        headers: headers.clone(),
+      },
+    );
+    self.save_manifest(&manifest)
+  }
+
+  /// Resolve a URL to its cached body, consulting the manifest first and
+  /// falling back to the global [`DiskCache`] when the URL has not been
+  /// vendored.
+  pub fn get(&self, url: &Url) -> io::Result<Vec<u8>> {
+    let manifest = self.load_manifest()?;
+    if let Some(entry) = manifest.get(url.as_str()) {
+      return fs::read(self.vendor_path.join(&entry.path));
+    }
+
+    let filename = self.fallback.get_cache_filename(url)?;
+    self.fallback.get(&filename)
+  }
+
+  /// Look up the stored response headers for a vendored URL, if any.
+  pub fn headers_for(&self, url: &Url) -> io::Result<Option<HashMap<String, String>>> {
+    let manifest = self.load_manifest()?;
+    Ok(manifest.get(url.as_str()).map(|e| e.headers.clone()))
+  }
+}
+
+/// Replace characters that cannot appear in a path segment on common
+/// filesystems. The manifest remains the authoritative URL→path mapping.
+fn sanitize_segment(segment: &str) -> String {
+  segment
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+      c => c,
+    })
+    .collect()
+}
+
+/// Append a short hash of the full URL to a leaf filename, preserving the
+/// original extension, so the readable layout stays injective across URLs that
+/// share a path but differ in query string, fragment, or trailing slash.
+fn unique_leaf(leaf: &str, url: &Url) -> String {
+  let hash = short_url_hash(url);
+  let leaf_path = Path::new(leaf);
+  match leaf_path.extension().and_then(OsStr::to_str) {
+    Some(ext) => {
+      let stem = leaf_path
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or(leaf);
+      format!("{}-{}.{}", stem, hash, ext)
+    }
+    None => format!("{}-{}", leaf, hash),
+  }
+}
+
+fn short_url_hash(url: &Url) -> String {
+  use std::hash::Hash;
+  use std::hash::Hasher;
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  url.as_str().hash(&mut hasher);
+  format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn readable(url: &str) -> PathBuf {
+    LocalDiskCache::readable_relative_path(&Url::parse(url).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn readable_path_preserves_extension() {
+    let path = readable("https://example.com/contracts/token.clar");
+    assert_eq!(path.extension().unwrap(), "clar");
+    assert!(path.starts_with("example.com/contracts"));
+  }
+
+  #[test]
+  fn readable_path_trailing_slash_gets_index_leaf() {
+    let slashed = readable("https://example.com/a/b/");
+    assert!(slashed.file_name().unwrap().to_string_lossy().starts_with("index-"));
+    assert!(slashed.starts_with("example.com/a/b"));
+  }
+
+  #[test]
+  fn readable_path_distinguishes_trailing_slash_from_literal_index() {
+    // `https://h/` and `https://h/index` both synthesize/name an `index` leaf;
+    // the full-URL hash must keep them on separate files.
+    assert_ne!(readable("https://h/"), readable("https://h/index"));
+  }
+
+  #[test]
+  fn readable_path_distinguishes_query_strings() {
+    // Query params differ but the path is identical: must not collide.
+    assert_ne!(
+      readable("https://h/c.clar?v=1"),
+      readable("https://h/c.clar?v=2"),
+    );
+    // Fragments too.
+    assert_ne!(readable("https://h/c.clar"), readable("https://h/c.clar#a"));
+  }
+
+  use tempfile::TempDir;
+
+  fn headers(pairs: &[(&str, &str)]) -> std::collections::HashMap<String, String> {
+    pairs
+      .iter()
+      .map(|(k, v)| (k.to_string(), v.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn http_metadata_round_trips_with_case_insensitive_headers() {
+    let dir = TempDir::new().unwrap();
+    let cache = DiskCache::new(dir.path()).unwrap();
+    let url = Url::parse("https://example.com/c.clar").unwrap();
+
+    // Mixed-case header names, as a fetcher preserving wire casing might send.
+    let headers = headers(&[
+      ("ETag", "\"abc\""),
+      ("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+      ("Cache-Control", "max-age=60"),
+    ]);
+    cache.set_http(&url, 200, &headers, b"body").unwrap();
+
+    let cached = cache.get_http(&url).unwrap();
+    assert_eq!(cached.body, b"body");
+    assert_eq!(cached.metadata.status, 200);
+    assert_eq!(cached.metadata.etag.as_deref(), Some("\"abc\""));
+    assert_eq!(
+      cached.metadata.last_modified.as_deref(),
+      Some("Wed, 21 Oct 2015 07:28:00 GMT")
+    );
+    assert_eq!(cached.metadata.cache_control.as_deref(), Some("max-age=60"));
+  }
+
+  #[test]
+  fn revalidate_keeps_body_and_refreshes_metadata() {
+    let dir = TempDir::new().unwrap();
+    let cache = DiskCache::new(dir.path()).unwrap();
+    let url = Url::parse("https://example.com/c.clar").unwrap();
+
+    cache
+      .set_http(&url, 200, &headers(&[("ETag", "\"v1\"")]), b"body")
+      .unwrap();
+
+    // Backdate the stored timestamp so that the refresh is observable.
+    let mut stale = cache.get_http(&url).unwrap().metadata;
+    stale.cached_at = 0;
+    let metadata_filename = cache.metadata_filename(&url).unwrap();
+    cache
+      .set(&metadata_filename, &serde_json::to_vec(&stale).unwrap())
+      .unwrap();
+
+    // A 304 revalidation must keep the cached body in place.
+    cache.revalidate_http(&url).unwrap();
+
+    let cached = cache.get_http(&url).unwrap();
+    assert_eq!(cached.body, b"body");
+    assert_eq!(cached.metadata.etag.as_deref(), Some("\"v1\""));
+    assert!(cached.metadata.cached_at > 0);
+  }
 }